@@ -1,8 +1,12 @@
-use async_std::task::{self, JoinHandle};
+use async_std::{
+    channel::{self, Receiver},
+    future, stream,
+    task::{self, JoinHandle},
+};
 use clap::Parser;
 use color_eyre::Result;
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
-use futures_util::{pin_mut, stream::StreamExt};
+use crossterm::event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind};
+use futures_util::{pin_mut, select, stream::StreamExt, FutureExt};
 use mdns::{discover, Record, RecordKind};
 use ratatui::{
     prelude::{Buffer, Constraint, Layout, Rect},
@@ -12,12 +16,12 @@ use ratatui::{
     widgets::{Block, Paragraph, Row, Table, Widget},
     DefaultTerminal, Frame,
 };
+use serde::{Deserialize, Serialize};
 use std::{
     fmt::{self, Display, Formatter},
-    mem,
+    fs, mem,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
-    sync::{Arc, Mutex},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 /// Simple TUI for discovering mDNS capable devices
@@ -26,170 +30,432 @@ use std::{
 struct Args {
     /// The mDNS query, e.g., "_http._tcp.local"
     query: Option<String>,
+
+    /// Scan once and print the results in this format instead of launching the TUI
+    #[arg(long, value_enum)]
+    output: Option<OutputFormat>,
+
+    /// How long to sweep for responses when using `--output`, in seconds
+    #[arg(long, default_value_t = 5)]
+    timeout: u64,
+}
+
+/// Machine-readable export formats for `--output` and the in-TUI dump key.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum OutputFormat {
+    Json,
+    Csv,
+}
+
+impl OutputFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Csv => "csv",
+        }
+    }
+}
+
+/// The DNS-SD meta-query that enumerates every advertised service type.
+const SERVICE_TYPES_QUERY: &str = "_services._dns-sd._udp.local";
+
+/// Which level of the "what services exist" -> "which devices offer this one" drill-down
+/// the app is currently showing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Browsing the service types advertised on the network.
+    #[default]
+    Types,
+    /// Browsing the resolved instances of `App::query`.
+    Instances,
 }
 
 #[derive(Debug, Ord, PartialOrd, PartialEq, Eq, Clone)]
 enum RecordEntry {
     A(Ipv4Addr, String),
     AAAA(Ipv6Addr, String),
+    /// A service type was advertised under an instance name (PTR: service -> instance)
+    Ptr(String, String),
+    /// An instance advertised a target host, port, priority and weight
+    Srv(String, String, u16, u16, u16),
+    /// An instance advertised `key=value` metadata
+    Txt(String, Vec<(String, String)>),
+}
+
+/// A record paired with the `Instant` its TTL expires at, so stale entries can age out.
+#[derive(Debug, Clone)]
+struct TimedEntry {
+    entry: RecordEntry,
+    expires_at: Instant,
 }
 
 #[derive(Default, Debug, Clone)]
 struct RecordEntries {
-    entries: Vec<RecordEntry>,
+    entries: Vec<TimedEntry>,
 }
 
 impl RecordEntry {
-    fn new(ip: IpAddr, name: String) -> Self {
-        match ip {
-            IpAddr::V4(addr) => RecordEntry::A(addr, name),
-            IpAddr::V6(addr) => RecordEntry::AAAA(addr, name),
-        }
-    }
-
-    fn get_name(self) -> String {
-        match self {
-            RecordEntry::A(_, name) => name,
-            RecordEntry::AAAA(_, name) => name,
+    /// Parses the raw mDNS `Record` into a `RecordEntry`, if it's a kind we resolve.
+    fn from_record(record: &Record) -> Option<Self> {
+        match &record.kind {
+            RecordKind::A(addr) => Some(RecordEntry::A(*addr, record.name.clone())),
+            RecordKind::AAAA(addr) => Some(RecordEntry::AAAA(*addr, record.name.clone())),
+            RecordKind::PTR(instance) => {
+                Some(RecordEntry::Ptr(record.name.clone(), instance.clone()))
+            }
+            RecordKind::SRV {
+                priority,
+                weight,
+                port,
+                target,
+            } => Some(RecordEntry::Srv(
+                record.name.clone(),
+                target.clone(),
+                *port,
+                *priority,
+                *weight,
+            )),
+            RecordKind::TXT(strings) => {
+                Some(RecordEntry::Txt(record.name.clone(), parse_txt(strings)))
+            }
+            _ => None,
         }
     }
+}
 
-    fn is_ipv4(self) -> bool {
-        match self {
-            RecordEntry::A(_, _) => true,
-            RecordEntry::AAAA(_, _) => false,
-        }
-    }
+/// Splits `key=value` TXT strings into pairs, keeping bare strings as `(key, "")`.
+fn parse_txt(strings: &[String]) -> Vec<(String, String)> {
+    strings
+        .iter()
+        .map(|s| match s.split_once('=') {
+            Some((k, v)) => (k.to_string(), v.to_string()),
+            None => (s.clone(), String::new()),
+        })
+        .collect()
+}
 
-    fn is_ipv6(self) -> bool {
+impl Display for RecordEntry {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
-            RecordEntry::A(_, _) => false,
-            RecordEntry::AAAA(_, _) => true,
+            RecordEntry::A(addr, name) => write!(f, "{}: {}", name, addr),
+            RecordEntry::AAAA(addr, name) => write!(f, "{}: {}", name, addr),
+            RecordEntry::Ptr(service, instance) => write!(f, "{} -> {}", service, instance),
+            RecordEntry::Srv(instance, target, port, _, _) => {
+                write!(f, "{}: {}:{}", instance, target, port)
+            }
+            RecordEntry::Txt(instance, pairs) => {
+                let rendered: Vec<String> =
+                    pairs.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+                write!(f, "{}: {}", instance, rendered.join(", "))
+            }
         }
     }
+}
 
-    fn get_addr(self) -> IpAddr {
-        match self {
-            RecordEntry::A(addr, _) => IpAddr::V4(addr),
-            RecordEntry::AAAA(addr, _) => IpAddr::V6(addr),
-        }
-    }
+/// A service instance resolved by joining its PTR, SRV, TXT and A/AAAA records.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct ServiceInstance {
+    #[serde(rename = "instance")]
+    name: String,
+    service_type: Option<String>,
+    #[serde(rename = "host")]
+    target: Option<String>,
+    port: Option<u16>,
+    #[serde(rename = "addresses")]
+    addrs: Vec<IpAddr>,
+    txt: Vec<(String, String)>,
 }
 
-impl Display for RecordEntry {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        let (addr, name) = match self {
-            RecordEntry::A(addr, name) => (format!("{}", addr), name),
-            RecordEntry::AAAA(addr, name) => (format!("{}", addr), name),
-        };
-        write!(f, "{}: {}", name, addr)
+/// Serializes resolved service instances to JSON or CSV for non-interactive use.
+fn serialize_instances(instances: &[ServiceInstance], format: OutputFormat) -> Result<String> {
+    match format {
+        OutputFormat::Json => Ok(serde_json::to_string_pretty(instances)?),
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(vec![]);
+            writer.write_record([
+                "instance",
+                "service_type",
+                "host",
+                "port",
+                "addresses",
+                "txt",
+            ])?;
+            for instance in instances {
+                writer.write_record([
+                    instance.name.clone(),
+                    instance.service_type.clone().unwrap_or_default(),
+                    instance.target.clone().unwrap_or_default(),
+                    instance.port.map(|p| p.to_string()).unwrap_or_default(),
+                    instance
+                        .addrs
+                        .iter()
+                        .map(|a| a.to_string())
+                        .collect::<Vec<String>>()
+                        .join(";"),
+                    instance
+                        .txt
+                        .iter()
+                        .map(|(k, v)| format!("{}={}", k, v))
+                        .collect::<Vec<String>>()
+                        .join(";"),
+                ])?;
+            }
+            Ok(String::from_utf8(writer.into_inner()?)?)
+        }
     }
 }
 
 impl RecordEntries {
-    fn find(self, name: String) -> (Option<IpAddr>, Option<IpAddr>) {
-        let mut remaining = self.entries.clone();
-        remaining.retain(|r| r.clone().get_name() == name);
-
-        let mut ipv4 = remaining.clone();
-        ipv4.retain(|r| r.clone().is_ipv4());
+    /// Joins PTR/SRV/TXT/A/AAAA records into one row per advertised service instance.
+    fn resolve_instances(&self) -> Vec<ServiceInstance> {
+        let mut instances: Vec<String> = self
+            .entries
+            .iter()
+            .filter_map(|t| match &t.entry {
+                RecordEntry::Ptr(_, instance) => Some(instance.clone()),
+                RecordEntry::Srv(instance, _, _, _, _) => Some(instance.clone()),
+                RecordEntry::Txt(instance, _) => Some(instance.clone()),
+                _ => None,
+            })
+            .collect();
+        instances.sort();
+        instances.dedup();
+
+        instances
+            .into_iter()
+            .map(|name| {
+                let srv = self.entries.iter().find_map(|t| match &t.entry {
+                    RecordEntry::Srv(instance, target, port, _, _) if *instance == name => {
+                        Some((target.clone(), *port))
+                    }
+                    _ => None,
+                });
+
+                let service_type = self.entries.iter().find_map(|t| match &t.entry {
+                    RecordEntry::Ptr(service, instance) if *instance == name => {
+                        Some(service.clone())
+                    }
+                    _ => None,
+                });
+
+                let txt = self
+                    .entries
+                    .iter()
+                    .find_map(|t| match &t.entry {
+                        RecordEntry::Txt(instance, pairs) if *instance == name => {
+                            Some(pairs.clone())
+                        }
+                        _ => None,
+                    })
+                    .unwrap_or_default();
+
+                let addrs = match &srv {
+                    Some((target, _)) => self
+                        .entries
+                        .iter()
+                        .filter_map(|t| match &t.entry {
+                            RecordEntry::A(addr, host) if host == target => Some(IpAddr::V4(*addr)),
+                            RecordEntry::AAAA(addr, host) if host == target => {
+                                Some(IpAddr::V6(*addr))
+                            }
+                            _ => None,
+                        })
+                        .collect(),
+                    None => Vec::new(),
+                };
 
-        let mut ipv6 = remaining.clone();
-        ipv6.retain(|r| r.clone().is_ipv6());
+                ServiceInstance {
+                    name,
+                    service_type,
+                    target: srv.as_ref().map(|(target, _)| target.clone()),
+                    port: srv.map(|(_, port)| port),
+                    addrs,
+                    txt,
+                }
+            })
+            .collect()
+    }
 
-        let v4 = match ipv4.pop() {
-            Some(ip) => Some(ip.get_addr()),
-            None => None,
-        };
+    /// The distinct service types advertised in response to `SERVICE_TYPES_QUERY`.
+    fn service_types(&self) -> Vec<String> {
+        let mut types: Vec<String> = self
+            .entries
+            .iter()
+            .filter_map(|t| match &t.entry {
+                RecordEntry::Ptr(_, service_type) => Some(service_type.clone()),
+                _ => None,
+            })
+            .collect();
+        types.sort();
+        types.dedup();
+        types
+    }
 
-        let v6 = match ipv6.pop() {
-            Some(ip) => Some(ip.get_addr()),
-            None => None,
-        };
+    /// Replaces any existing record that the new one supersedes (or removes it outright on a
+    /// TTL=0 goodbye packet), then inserts the new record with a freshly re-armed expiry.
+    fn upsert(&mut self, entry: RecordEntry, ttl: u32) {
+        self.entries.retain(|t| match (&t.entry, &entry) {
+            (RecordEntry::A(_, n), RecordEntry::A(_, name)) => n != name,
+            (RecordEntry::AAAA(_, n), RecordEntry::AAAA(_, name)) => n != name,
+            (RecordEntry::Ptr(s, i), RecordEntry::Ptr(service, instance)) => {
+                s != service || i != instance
+            }
+            (RecordEntry::Srv(i, ..), RecordEntry::Srv(instance, ..)) => i != instance,
+            (RecordEntry::Txt(i, _), RecordEntry::Txt(instance, _)) => i != instance,
+            _ => true,
+        });
+        if ttl == 0 {
+            // A goodbye packet: the entry is removed above and never re-inserted.
+            return;
+        }
+        self.entries.push(TimedEntry {
+            entry,
+            expires_at: Instant::now() + Duration::from_secs(ttl as u64),
+        });
+        self.entries.sort_by(|a, b| a.entry.cmp(&b.entry));
+    }
 
-        (v4, v6)
+    /// Drops every entry whose TTL has elapsed.
+    fn expire(&mut self) {
+        let now = Instant::now();
+        self.entries.retain(|t| t.expires_at > now);
     }
 }
 
 impl Display for RecordEntries {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        for r in self.entries.clone() {
-            write!(f, "{}\n", r)?;
+        for t in self.entries.clone() {
+            write!(f, "{}\n", t.entry)?;
         }
         Ok(())
     }
 }
 
-#[derive(Debug, Default)]
+/// The distinct outcomes a single tick of the main select loop can produce.
+enum Tick {
+    Input(Option<std::io::Result<Event>>),
+    Discovered(Option<(RecordEntry, u32)>),
+    Repaint,
+}
+
+#[derive(Default)]
 pub struct App {
     exit: bool,
-    records: Arc<Mutex<RecordEntries>>,
+    records: RecordEntries,
     query: String,
     editing: bool,
-    child: Option<JoinHandle<()>>,
+    scanner: Option<JoinHandle<()>>,
+    discovered: Option<Receiver<(RecordEntry, u32)>>,
+    mode: Mode,
+    selected: usize,
+    /// Result of the last `e`/`c` export, shown in the status line until the next one.
+    status: Option<String>,
 }
 
 impl App {
     async fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        let mut input = EventStream::new().fuse();
+        let mut repaint = stream::interval(Duration::from_millis(250)).fuse();
+
+        terminal.draw(|frame| self.draw(frame))?;
+
         while !self.exit {
-            terminal.draw(|frame| self.draw(frame))?;
-            match event::poll(Duration::from_millis(8)) {
-                Ok(true) => self.handle_events().await?,
-                _ => {}
+            let mut discovered = self.discovered.take();
+            let tick = match discovered.as_mut() {
+                Some(rx) => {
+                    select! {
+                        ev = input.next() => Tick::Input(ev),
+                        rec = rx.next().fuse() => Tick::Discovered(rec),
+                        _ = repaint.next() => Tick::Repaint,
+                    }
+                }
+                None => {
+                    select! {
+                        ev = input.next() => Tick::Input(ev),
+                        _ = repaint.next() => Tick::Repaint,
+                    }
+                }
+            };
+            self.discovered = discovered;
+
+            match tick {
+                Tick::Input(Some(Ok(Event::Key(key_event))))
+                    if key_event.kind == KeyEventKind::Press =>
+                {
+                    self.handle_key_event(key_event).await
+                }
+                Tick::Input(_) => {}
+                Tick::Discovered(Some((entry, ttl))) => self.records.upsert(entry, ttl),
+                Tick::Discovered(None) => self.discovered = None,
+                Tick::Repaint => {}
             }
+
+            self.records.expire();
+            terminal.draw(|frame| self.draw(frame))?;
         }
-        if let Some(c) = mem::take(&mut self.child) {
-            c.cancel().await;
+
+        if let Some(scanner) = mem::take(&mut self.scanner) {
+            scanner.cancel().await;
         }
         Ok(())
     }
 
     async fn start_scanner(&mut self) {
         let query = self.query.clone();
-        let records: Arc<Mutex<RecordEntries>> = Arc::clone(&self.records);
+        let (tx, rx) = channel::unbounded();
+        self.discovered = Some(rx);
 
-        if let Some(c) = mem::take(&mut self.child) {
-            c.cancel().await;
+        if let Some(scanner) = mem::take(&mut self.scanner) {
+            scanner.cancel().await;
         }
 
-        self.child = Some(task::spawn(async move {
+        self.scanner = Some(task::spawn(async move {
             let stream = discover::all(query, Duration::from_secs(5))
                 .unwrap()
                 .listen();
             pin_mut!(stream);
 
             while let Some(Ok(response)) = stream.next().await {
-                let res: Vec<(IpAddr, String)> =
-                    response.records().filter_map(self::to_ip_addr).collect();
-
-                for (addr, name) in res {
-                    records.lock().unwrap().entries.retain(|r| !match r {
-                        RecordEntry::A(_, n) => addr.is_ipv4() && *n == name,
-                        RecordEntry::AAAA(_, n) => addr.is_ipv6() && *n == name,
-                    });
-                    records
-                        .lock()
-                        .unwrap()
-                        .entries
-                        .push(RecordEntry::new(addr, name));
+                for record in response.records() {
+                    if let Some(entry) = RecordEntry::from_record(record) {
+                        if tx.send((entry, record.ttl)).await.is_err() {
+                            return;
+                        }
+                    }
                 }
-                records.lock().unwrap().entries.sort();
             }
         }));
     }
 
-    fn draw(&self, frame: &mut Frame) {
-        frame.render_widget(self, frame.area());
+    /// Issues the DNS-SD meta-query and switches to the service-type browser.
+    async fn start_browse(&mut self) {
+        self.mode = Mode::Types;
+        self.selected = 0;
+        self.query = SERVICE_TYPES_QUERY.to_string();
+        self.start_scanner().await;
+        self.records.entries.clear();
     }
 
-    async fn handle_events(&mut self) -> Result<()> {
-        match event::read()? {
-            Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
-                self.handle_key_event(key_event).await
-            }
-            _ => {}
-        };
-        Ok(())
+    /// Drills down into a specific service type, showing its resolved instances.
+    async fn drill_into(&mut self, service_type: String) {
+        self.mode = Mode::Instances;
+        self.selected = 0;
+        self.query = service_type;
+        self.start_scanner().await;
+        self.records.entries.clear();
+    }
+
+    /// Writes the currently resolved instances to `mdns-export.<ext>` in the working directory,
+    /// returning the path written on success.
+    fn dump(&self, format: OutputFormat) -> Result<String> {
+        let serialized = serialize_instances(&self.records.resolve_instances(), format)?;
+        let path = format!("mdns-export.{}", format.extension());
+        fs::write(&path, serialized)?;
+        Ok(path)
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        frame.render_widget(self, frame.area());
     }
 
     async fn handle_key_event(&mut self, key_event: KeyEvent) {
@@ -198,14 +464,44 @@ impl App {
                 KeyCode::Char('q') => self.exit = true,
                 KeyCode::Esc => self.exit = true,
                 KeyCode::Char('/') => self.editing = true,
+                KeyCode::Char('b') => self.start_browse().await,
+                KeyCode::Char('e') => {
+                    self.status = Some(match self.dump(OutputFormat::Json) {
+                        Ok(path) => format!("Exported to {}", path),
+                        Err(e) => format!("Export failed: {}", e),
+                    });
+                }
+                KeyCode::Char('c') => {
+                    self.status = Some(match self.dump(OutputFormat::Csv) {
+                        Ok(path) => format!("Exported to {}", path),
+                        Err(e) => format!("Export failed: {}", e),
+                    });
+                }
+                KeyCode::Down | KeyCode::Char('j') if self.mode == Mode::Types => {
+                    let len = self.records.service_types().len();
+                    if len > 0 {
+                        self.selected = (self.selected + 1).min(len - 1);
+                    }
+                }
+                KeyCode::Up | KeyCode::Char('k') if self.mode == Mode::Types => {
+                    self.selected = self.selected.saturating_sub(1);
+                }
+                KeyCode::Enter if self.mode == Mode::Types => {
+                    let types = self.records.service_types();
+                    if let Some(service_type) = types.get(self.selected).cloned() {
+                        self.drill_into(service_type).await;
+                    }
+                }
                 _ => {}
             },
             true => match key_event.code {
                 KeyCode::Char(c) => self.query.push(c),
                 KeyCode::Esc => {
                     self.editing = false;
+                    self.mode = Mode::Instances;
+                    self.selected = 0;
                     self.start_scanner().await;
-                    self.records.lock().unwrap().entries.clear();
+                    self.records.entries.clear();
                 }
                 KeyCode::Backspace => {
                     self.query.pop().unwrap_or('a');
@@ -213,8 +509,10 @@ impl App {
                 }
                 KeyCode::Enter => {
                     self.editing = false;
+                    self.mode = Mode::Instances;
+                    self.selected = 0;
                     self.start_scanner().await;
-                    self.records.lock().unwrap().entries.clear();
+                    self.records.entries.clear();
                 }
                 _ => {}
             },
@@ -229,11 +527,18 @@ impl Widget for &App {
             .border_set(border::THICK);
 
         let table_block = Block::bordered()
-            .title(Line::from(" Records ".bold()))
+            .title(Line::from(match self.mode {
+                Mode::Types => " Service Types (Enter to browse, / to query) ".bold(),
+                Mode::Instances => " Services ".bold(),
+            }))
             .border_set(border::THICK);
 
-        let [search_area, table_area] =
-            Layout::vertical([Constraint::Length(3), Constraint::Fill(1)]).areas(area);
+        let [search_area, table_area, status_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+        ])
+        .areas(area);
 
         Paragraph::new(match self.editing {
             false => self.query.clone(),
@@ -242,66 +547,146 @@ impl Widget for &App {
         .block(search_block)
         .render(search_area, buf);
 
-        let records: RecordEntries = self.records.lock().unwrap().clone();
-        let mut hosts: Vec<String> = records
-            .entries
-            .iter()
-            .map(|r| r.clone().get_name())
-            .collect();
-        let mut seen: Vec<String> = Vec::new();
+        Paragraph::new(self.status.clone().unwrap_or_default()).render(status_area, buf);
+
+        let records: RecordEntries = self.records.clone();
+
+        match self.mode {
+            Mode::Types => {
+                let types = records.service_types();
+                let rows: Vec<Row> = types
+                    .iter()
+                    .enumerate()
+                    .map(|(i, service_type)| {
+                        let row = Row::new(vec![service_type.clone()]);
+                        if i == self.selected {
+                            row.on_blue()
+                        } else {
+                            row
+                        }
+                    })
+                    .collect();
+                let widths = [Constraint::Percentage(100)];
+                Table::new(rows, widths)
+                    .header(Row::new(vec!["Service Type"]).bold().bottom_margin(1))
+                    .block(table_block)
+                    .render(table_area, buf);
+            }
+            Mode::Instances => {
+                let instances = records.resolve_instances();
+                let rows: Vec<Row> = instances
+                    .iter()
+                    .map(|instance| {
+                        let addrs = if instance.addrs.is_empty() {
+                            String::from("Not found")
+                        } else {
+                            instance
+                                .addrs
+                                .iter()
+                                .map(|a| a.to_string())
+                                .collect::<Vec<String>>()
+                                .join(", ")
+                        };
+                        let port = match instance.port {
+                            Some(p) => p.to_string(),
+                            None => String::new(),
+                        };
+                        let txt = instance
+                            .txt
+                            .iter()
+                            .map(|(k, v)| format!("{}={}", k, v))
+                            .collect::<Vec<String>>()
+                            .join(", ");
+                        Row::new(vec![instance.name.clone(), addrs, port, txt])
+                    })
+                    .collect();
+                let widths = [
+                    Constraint::Percentage(35),
+                    Constraint::Percentage(25),
+                    Constraint::Percentage(10),
+                    Constraint::Percentage(30),
+                ];
+                Table::new(rows, widths)
+                    .header(
+                        Row::new(vec!["Instance", "Address", "Port", "TXT"])
+                            .bold()
+                            .bottom_margin(1),
+                    )
+                    .block(table_block)
+                    .render(table_area, buf);
+            }
+        }
+    }
+}
 
-        hosts.retain(|h| {
-            let r = !seen.contains(h);
-            seen.push(h.clone());
-            r
-        });
+/// Runs a single discovery sweep without a terminal, for `--output`. `discover::all`'s
+/// duration only controls the re-query interval and never ends the stream on its own, so
+/// the sweep is bounded separately by `timeout`.
+async fn scan(query: String, timeout: Duration) -> Result<RecordEntries> {
+    let mut records = RecordEntries::default();
+    let stream = discover::all(query, Duration::from_secs(5))?.listen();
+    pin_mut!(stream);
+
+    let sweep = async {
+        while let Some(Ok(response)) = stream.next().await {
+            for record in response.records() {
+                if let Some(entry) = RecordEntry::from_record(record) {
+                    records.upsert(entry, record.ttl);
+                }
+            }
+        }
+    };
+    let _ = future::timeout(timeout, sweep).await;
+    records.expire();
 
-        let rows: Vec<Row> = hosts
-            .iter()
-            .map(|h| {
-                let (ipv4, ipv6) = records.clone().find(h.clone());
-                let v4 = match ipv4 {
-                    Some(ip) => format!("{:?}", ip),
-                    None => String::from("Not found"),
-                };
-                let v6 = match ipv6 {
-                    Some(ip) => format!("{:?}", ip),
-                    None => String::from("Not found"),
-                };
-                Row::new(vec![String::from(h), v4, v6])
-            })
-            .collect();
-        let widths = [
-            Constraint::Percentage(40),
-            Constraint::Percentage(30),
-            Constraint::Percentage(30),
-        ];
-        Table::new(rows, widths)
-            .header(
-                Row::new(vec!["Host", "IPv4", "IPv6"])
-                    .bold()
-                    .bottom_margin(1),
-            )
-            .block(table_block)
-            .render(table_area, buf);
+    Ok(records)
+}
+
+/// Sweeps every service type advertised via the DNS-SD meta-query and resolves instances
+/// for each of them, for `--output` runs with no explicit query.
+async fn scan_all_service_types(timeout: Duration) -> Result<RecordEntries> {
+    let types = scan(SERVICE_TYPES_QUERY.to_string(), timeout)
+        .await?
+        .service_types();
+
+    let mut combined = RecordEntries::default();
+    for service_type in types {
+        combined
+            .entries
+            .extend(scan(service_type, timeout).await?.entries);
     }
+    Ok(combined)
 }
 
 #[async_std::main]
 async fn main() -> Result<()> {
+    let args = Args::parse();
+
+    if let Some(format) = args.output {
+        let timeout = Duration::from_secs(args.timeout);
+        let records = match args.query {
+            Some(q) => scan(q, timeout).await?,
+            None => scan_all_service_types(timeout).await?,
+        };
+        println!(
+            "{}",
+            serialize_instances(&records.resolve_instances(), format)?
+        );
+        return Ok(());
+    }
+
     color_eyre::install()?;
     let mut terminal = ratatui::init();
     terminal.clear()?;
 
-    let args = Args::parse();
-
     let mut app = App::default();
     match args.query {
         Some(q) => {
+            app.mode = Mode::Instances;
             app.query = q;
             app.start_scanner().await;
         }
-        None => app.query = String::from(""),
+        None => app.start_browse().await,
     };
     app.editing = false;
 
@@ -311,10 +696,196 @@ async fn main() -> Result<()> {
     result
 }
 
-fn to_ip_addr(record: &Record) -> Option<(IpAddr, String)> {
-    match record.kind {
-        RecordKind::A(addr) => Some((addr.into(), record.name.clone())),
-        RecordKind::AAAA(addr) => Some((addr.into(), record.name.clone())),
-        _ => None,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_txt_splits_key_value_pairs() {
+        let strings = vec!["path=/".to_string(), "version=1.0".to_string()];
+        assert_eq!(
+            parse_txt(&strings),
+            vec![
+                ("path".to_string(), "/".to_string()),
+                ("version".to_string(), "1.0".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_txt_keeps_bare_strings_with_an_empty_value() {
+        let strings = vec!["no-equals-sign".to_string()];
+        assert_eq!(
+            parse_txt(&strings),
+            vec![("no-equals-sign".to_string(), String::new())]
+        );
+    }
+
+    #[test]
+    fn resolve_instances_joins_ptr_srv_txt_and_a_records() {
+        let mut records = RecordEntries::default();
+        records.upsert(
+            RecordEntry::Ptr("_http._tcp.local".to_string(), "My Printer".to_string()),
+            120,
+        );
+        records.upsert(
+            RecordEntry::Srv(
+                "My Printer".to_string(),
+                "printer.local".to_string(),
+                8080,
+                0,
+                0,
+            ),
+            120,
+        );
+        records.upsert(
+            RecordEntry::Txt(
+                "My Printer".to_string(),
+                vec![("path".to_string(), "/".to_string())],
+            ),
+            120,
+        );
+        records.upsert(
+            RecordEntry::A(Ipv4Addr::new(192, 168, 1, 2), "printer.local".to_string()),
+            120,
+        );
+
+        let instances = records.resolve_instances();
+
+        assert_eq!(instances.len(), 1);
+        let instance = &instances[0];
+        assert_eq!(instance.name, "My Printer");
+        assert_eq!(instance.service_type.as_deref(), Some("_http._tcp.local"));
+        assert_eq!(instance.target.as_deref(), Some("printer.local"));
+        assert_eq!(instance.port, Some(8080));
+        assert_eq!(
+            instance.addrs,
+            vec![IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2))]
+        );
+        assert_eq!(instance.txt, vec![("path".to_string(), "/".to_string())]);
+    }
+
+    #[test]
+    fn upsert_re_arms_ttl_on_re_announce() {
+        let mut records = RecordEntries::default();
+        records.upsert(
+            RecordEntry::A(Ipv4Addr::new(10, 0, 0, 1), "host.local".to_string()),
+            120,
+        );
+
+        // Simulate the first announcement's TTL having already elapsed.
+        records.entries[0].expires_at = Instant::now() - Duration::from_secs(1);
+        assert_eq!(records.entries.len(), 1);
+
+        records.upsert(
+            RecordEntry::A(Ipv4Addr::new(10, 0, 0, 1), "host.local".to_string()),
+            120,
+        );
+
+        assert_eq!(records.entries.len(), 1);
+        assert!(records.entries[0].expires_at > Instant::now());
+    }
+
+    #[test]
+    fn upsert_removes_the_entry_on_a_ttl_zero_goodbye() {
+        let mut records = RecordEntries::default();
+        records.upsert(
+            RecordEntry::A(Ipv4Addr::new(10, 0, 0, 1), "host.local".to_string()),
+            120,
+        );
+        assert_eq!(records.entries.len(), 1);
+
+        records.upsert(
+            RecordEntry::A(Ipv4Addr::new(10, 0, 0, 1), "host.local".to_string()),
+            0,
+        );
+
+        assert!(records.entries.is_empty());
+    }
+
+    #[test]
+    fn expire_drops_only_entries_past_their_ttl() {
+        let mut records = RecordEntries::default();
+        records.upsert(
+            RecordEntry::A(Ipv4Addr::new(10, 0, 0, 1), "stale.local".to_string()),
+            120,
+        );
+        records.upsert(
+            RecordEntry::A(Ipv4Addr::new(10, 0, 0, 2), "fresh.local".to_string()),
+            120,
+        );
+        records.entries.iter_mut().for_each(|t| {
+            if let RecordEntry::A(_, name) = &t.entry {
+                if name == "stale.local" {
+                    t.expires_at = Instant::now() - Duration::from_secs(1);
+                }
+            }
+        });
+
+        records.expire();
+
+        assert_eq!(records.entries.len(), 1);
+        match &records.entries[0].entry {
+            RecordEntry::A(_, name) => assert_eq!(name, "fresh.local"),
+            other => panic!("unexpected entry: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn serialize_instances_csv_has_a_stable_header_and_row_shape() {
+        let instances = vec![
+            ServiceInstance {
+                name: "My Printer".to_string(),
+                service_type: Some("_http._tcp.local".to_string()),
+                target: Some("printer.local".to_string()),
+                port: Some(8080),
+                addrs: vec![IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2))],
+                txt: vec![("path".to_string(), "/".to_string())],
+            },
+            ServiceInstance::default(),
+        ];
+
+        let csv = serialize_instances(&instances, OutputFormat::Csv).unwrap();
+        let mut lines = csv.lines();
+
+        assert_eq!(
+            lines.next().unwrap(),
+            "instance,service_type,host,port,addresses,txt"
+        );
+        assert_eq!(
+            lines.next().unwrap(),
+            "My Printer,_http._tcp.local,printer.local,8080,192.168.1.2,path=/"
+        );
+        assert_eq!(lines.next().unwrap(), ",,,,,");
+        assert!(lines.next().is_none());
+    }
+
+    #[test]
+    fn serialize_instances_json_round_trips_populated_and_empty_instances() {
+        let populated = ServiceInstance {
+            name: "My Printer".to_string(),
+            service_type: Some("_http._tcp.local".to_string()),
+            target: Some("printer.local".to_string()),
+            port: Some(8080),
+            addrs: vec![IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2))],
+            txt: vec![("path".to_string(), "/".to_string())],
+        };
+        let empty = ServiceInstance::default();
+        let instances = vec![populated.clone(), empty.clone()];
+
+        let json = serialize_instances(&instances, OutputFormat::Json).unwrap();
+        let round_tripped: Vec<ServiceInstance> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(round_tripped.len(), 2);
+        assert_eq!(round_tripped[0].name, populated.name);
+        assert_eq!(round_tripped[0].service_type, populated.service_type);
+        assert_eq!(round_tripped[0].target, populated.target);
+        assert_eq!(round_tripped[0].port, populated.port);
+        assert_eq!(round_tripped[0].addrs, populated.addrs);
+        assert_eq!(round_tripped[0].txt, populated.txt);
+        assert_eq!(round_tripped[1].name, empty.name);
+        assert_eq!(round_tripped[1].service_type, empty.service_type);
+        assert!(round_tripped[1].addrs.is_empty());
+        assert!(round_tripped[1].txt.is_empty());
     }
 }